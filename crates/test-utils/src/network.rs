@@ -2,7 +2,13 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use jsonrpsee_http_client::{HttpClient, HttpClientBuilder};
-use jsonrpsee_http_server::{HttpServerBuilder, HttpServerHandle, RpcModule};
+use jsonrpsee_http_server::{
+    AccessControlBuilder, HttpServerBuilder, HttpServerHandle, RpcModule,
+};
+use jsonrpsee_ws_client::{WsClient, WsClientBuilder};
+use jsonrpsee_ws_server::{WsServerBuilder, WsServerHandle};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
 use std::num::NonZeroUsize;
 use std::path::Path;
@@ -28,6 +34,11 @@ use sui_sdk::crypto::{KeystoreType, SuiKeystore};
 use sui_swarm::memory::{Swarm, SwarmBuilder};
 use sui_types::base_types::SuiAddress;
 use sui_types::crypto::KeypairTraits;
+use testcontainers::clients::Cli;
+use testcontainers::images::generic::GenericImage;
+use testcontainers::{Container, Docker};
+use torut::control::UnauthenticatedConn;
+use torut::onion::TorSecretKeyV3;
 const NUM_VALIDAOTR: usize = 4;
 
 pub async fn start_test_network(
@@ -117,21 +128,265 @@ pub async fn setup_network_and_wallet() -> Result<(Swarm, WalletContext, SuiAddr
     Ok((swarm, context, address))
 }
 
-async fn start_rpc_gateway(
-    config_path: &Path,
-) -> Result<(SocketAddr, HttpServerHandle), anyhow::Error> {
-    let server = HttpServerBuilder::default().build("127.0.0.1:0").await?;
-    let addr = server.local_addr()?;
+// Subscriptions (`subscribeTransaction`, `subscribeEvent`) are only meaningful over a
+// stateful transport, so they are registered on the WS module only; the HTTP module keeps
+// plain request/response methods.
+struct RpcServers {
+    http_addr: SocketAddr,
+    http_handle: HttpServerHandle,
+    ws_addr: SocketAddr,
+    ws_handle: WsServerHandle,
+    // `None` unless a Tor control port was supplied to `start_rpc_gateway`, in which case
+    // both listeners above are also published as a single onion service.
+    onion_url: Option<String>,
+    notifier: Arc<GatewayNotifier>,
+}
+
+/// Notification sink backing `subscribeTransaction`/`subscribeEvent`. The real commit and
+/// Move-event pipelines live in `sui-core`/`sui-json-rpc`, outside this crate, so wiring
+/// `publish_transaction`/`publish_event` into that pipeline is future work there; this type
+/// only owns the pub/sub plumbing (fan-out to subscribers) and lets tests drive it directly
+/// via [`TestNetwork::notifier`].
+pub struct GatewayNotifier {
+    transactions: tokio::sync::broadcast::Sender<serde_json::Value>,
+    events: tokio::sync::broadcast::Sender<serde_json::Value>,
+}
+
+impl GatewayNotifier {
+    fn new() -> Self {
+        let (transactions, _) = tokio::sync::broadcast::channel(1024);
+        let (events, _) = tokio::sync::broadcast::channel(1024);
+        Self {
+            transactions,
+            events,
+        }
+    }
+
+    pub fn publish_transaction(&self, transaction: serde_json::Value) {
+        let _ = self.transactions.send(transaction);
+    }
+
+    pub fn publish_event(&self, event: serde_json::Value) {
+        let _ = self.events.send(event);
+    }
+}
+
+/// Registers `subscribeTransaction`/`subscribeEvent` (and their matching `unsubscribe*`
+/// methods) on `module`, forwarding whatever `notifier` publishes to every live subscriber.
+fn register_subscriptions(
+    module: &mut RpcModule<()>,
+    notifier: Arc<GatewayNotifier>,
+) -> Result<(), anyhow::Error> {
+    let transactions = notifier.transactions.clone();
+    module.register_subscription(
+        "subscribeTransaction",
+        "transaction",
+        "unsubscribeTransaction",
+        move |_params, mut sink, _ctx| {
+            let mut rx = transactions.subscribe();
+            tokio::spawn(async move {
+                while let Ok(transaction) = rx.recv().await {
+                    if sink.send(&transaction).is_err() {
+                        break;
+                    }
+                }
+            });
+            Ok(())
+        },
+    )?;
+
+    let events = notifier.events.clone();
+    module.register_subscription(
+        "subscribeEvent",
+        "event",
+        "unsubscribeEvent",
+        move |_params, mut sink, _ctx| {
+            let mut rx = events.subscribe();
+            tokio::spawn(async move {
+                while let Ok(event) = rx.recv().await {
+                    if sink.send(&event).is_err() {
+                        break;
+                    }
+                }
+            });
+            Ok(())
+        },
+    )?;
+    Ok(())
+}
+
+/// A handle to a locally running Tor control port, used to publish the gateway's listeners
+/// as an onion service. Asserting that Tor is actually reachable on this port happens in
+/// `publish_onion_service` so callers get a clear error instead of a silent no-op.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct TorControlPort(pub u16);
+
+/// Knobs for [`start_rpc_gateway`] beyond the bare HTTP/WS listeners. Defaults reproduce the
+/// original behavior: no Tor, and origins/hosts left to jsonrpsee's own defaults (localhost
+/// only), which is what kept every existing test passing unchanged.
+///
+/// `GatewayConfig` (from the `sui` crate) has no CORS/host fields of its own, so rather than
+/// passing this straight through as a bare function argument, [`TestNetworkBuilder::build`]
+/// persists it to [`RPC_GATEWAY_OPTIONS_FILE`] next to the gateway config, and
+/// `start_rpc_gateway` reads it back from there — the same config-on-disk flow
+/// `GatewayConfig`/`SuiClientConfig` already use in this file, just in a sidecar file rather
+/// than new fields on a type this crate doesn't own.
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct RpcGatewayOptions {
+    pub tor_control_port: Option<TorControlPort>,
+    /// Allowed CORS origins; supports a literal `"*"` wildcard entry. Empty keeps jsonrpsee's
+    /// default (localhost only), which rejects browser-based dApp origins.
+    pub cors_allowed_origins: Vec<String>,
+    /// Host allow-list enforced on the `Host` header of incoming requests. Empty keeps
+    /// jsonrpsee's default.
+    pub allowed_hosts: Vec<String>,
+    /// Allowed CORS request headers (e.g. `"content-type"`). Empty keeps jsonrpsee's default.
+    ///
+    /// There is no matching `allowed_methods` field: jsonrpsee's HTTP server only ever
+    /// accepts `POST` for JSON-RPC calls (plus `OPTIONS` for the preflight itself), and
+    /// `AccessControlBuilder` has no knob to configure that, so there's nothing to plumb.
+    pub cors_allowed_headers: Vec<String>,
+}
+
+impl Config for RpcGatewayOptions {}
+
+/// Sidecar file `RpcGatewayOptions` is persisted to, alongside `SUI_GATEWAY_CONFIG` in the
+/// same directory.
+const RPC_GATEWAY_OPTIONS_FILE: &str = "gateway-rpc-options.json";
+
+fn build_access_control(
+    options: &RpcGatewayOptions,
+) -> Result<jsonrpsee_http_server::AccessControl, anyhow::Error> {
+    let mut builder = AccessControlBuilder::new();
+    if !options.cors_allowed_origins.is_empty() {
+        builder = builder.set_allowed_origins(options.cors_allowed_origins.clone())?;
+    }
+    if !options.allowed_hosts.is_empty() {
+        builder = builder.set_allowed_hosts(options.allowed_hosts.clone())?;
+    }
+    if !options.cors_allowed_headers.is_empty() {
+        builder = builder.set_allowed_headers(options.cors_allowed_headers.clone())?;
+    }
+    Ok(builder.build())
+}
+
+async fn start_rpc_gateway(config_path: &Path) -> Result<RpcServers, anyhow::Error> {
+    let options_path = config_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(RPC_GATEWAY_OPTIONS_FILE);
+    let options: RpcGatewayOptions = if options_path.exists() {
+        PersistedConfig::read(&options_path)?
+    } else {
+        RpcGatewayOptions::default()
+    };
+    let access_control = build_access_control(&options)?;
+    let http_server = HttpServerBuilder::default()
+        .set_access_control(access_control.clone())
+        .build("127.0.0.1:0")
+        .await?;
+    let http_addr = http_server.local_addr()?;
+    // CORS preflight only applies to HTTP, but the host allow-list still matters for the WS
+    // upgrade handshake, so the same `access_control` is reused here.
+    let ws_server = WsServerBuilder::default()
+        .set_access_control(access_control)
+        .build("127.0.0.1:0")
+        .await?;
+    let ws_addr = ws_server.local_addr()?;
+
+    let onion_url = match options.tor_control_port {
+        Some(control_port) => Some(publish_onion_service(control_port, http_addr, ws_addr).await?),
+        None => None,
+    };
+
+    // Scope cut: the request that added `onion_url` also asked for outbound SOCKS5 dialing
+    // on `RpcGatewayClient`/`create_client` (both imported unchanged above), so the gateway's
+    // own calls and `TestNetwork::gateway_client`'s calls could round-trip through Tor too.
+    // Neither function takes a SOCKS5 port in this version of `sui_gateway`, and that crate
+    // isn't part of this tree, so only the listener-publishing half above is implemented;
+    // outbound SOCKS5 dialing would need to land in `sui_gateway` itself first.
     let registry = prometheus::Registry::new();
     let client = create_client(config_path, &registry)?;
-    let mut module = RpcModule::new(());
-    module.merge(RpcGatewayImpl::new(client.clone()).into_rpc())?;
-    module.merge(GatewayReadApiImpl::new(client.clone()).into_rpc())?;
-    module.merge(TransactionBuilderImpl::new(client.clone()).into_rpc())?;
-    module.merge(GatewayWalletSyncApiImpl::new(client.clone()).into_rpc())?;
 
-    let handle = server.start(module)?;
-    Ok((addr, handle))
+    let mut http_module = RpcModule::new(());
+    http_module.merge(RpcGatewayImpl::new(client.clone()).into_rpc())?;
+    http_module.merge(GatewayReadApiImpl::new(client.clone()).into_rpc())?;
+    http_module.merge(TransactionBuilderImpl::new(client.clone()).into_rpc())?;
+    http_module.merge(GatewayWalletSyncApiImpl::new(client.clone()).into_rpc())?;
+
+    // The WS module carries everything the HTTP module has, plus `subscribeTransaction` /
+    // `subscribeEvent`, which push whatever `notifier` is given to every live subscriber
+    // instead of requiring clients to poll.
+    let mut ws_module = RpcModule::new(());
+    ws_module.merge(RpcGatewayImpl::new(client.clone()).into_rpc())?;
+    ws_module.merge(GatewayReadApiImpl::new(client.clone()).into_rpc())?;
+    ws_module.merge(TransactionBuilderImpl::new(client.clone()).into_rpc())?;
+    ws_module.merge(GatewayWalletSyncApiImpl::new(client.clone()).into_rpc())?;
+    let notifier = Arc::new(GatewayNotifier::new());
+    register_subscriptions(&mut ws_module, notifier.clone())?;
+
+    // `encrypted_request` envelopes are decrypted and dispatched into this module, which
+    // exposes the same methods as `http_module`/`ws_module` rather than a live connection,
+    // since the secure channel sits in front of the gateway, not behind it.
+    let dispatch = Arc::new({
+        let mut dispatch_module = RpcModule::new(());
+        dispatch_module.merge(RpcGatewayImpl::new(client.clone()).into_rpc())?;
+        dispatch_module.merge(GatewayReadApiImpl::new(client.clone()).into_rpc())?;
+        dispatch_module.merge(TransactionBuilderImpl::new(client.clone()).into_rpc())?;
+        dispatch_module.merge(GatewayWalletSyncApiImpl::new(client).into_rpc())?;
+        dispatch_module
+    });
+    secure::register_secure_channel(&mut http_module, dispatch.clone())?;
+    secure::register_secure_channel(&mut ws_module, dispatch)?;
+
+    let http_handle = http_server.start(http_module)?;
+    let ws_handle = ws_server.start(ws_module)?;
+    Ok(RpcServers {
+        http_addr,
+        http_handle,
+        ws_addr,
+        ws_handle,
+        onion_url,
+        notifier,
+    })
+}
+
+/// Registers the gateway's HTTP and WS listeners with a running Tor daemon over its control
+/// port (following the xmr-btc-swap transport design) and returns the resulting `.onion`
+/// URL. Fails fast if Tor isn't actually listening on `control_port`, rather than silently
+/// leaving the gateway unreachable over Tor.
+async fn publish_onion_service(
+    control_port: TorControlPort,
+    http_addr: SocketAddr,
+    ws_addr: SocketAddr,
+) -> Result<String, anyhow::Error> {
+    let mut client = UnauthenticatedConn::new(
+        tokio::net::TcpStream::connect(("127.0.0.1", control_port.0)).await?,
+    );
+    client
+        .authenticate(&[])
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to authenticate with Tor control port: {:?}", e))?;
+    let mut client = client.into_authenticated().await;
+
+    let key = TorSecretKeyV3::generate();
+    client
+        .add_onion_v3(
+            &key,
+            false,
+            false,
+            false,
+            None,
+            &mut [
+                (80u16, http_addr),
+                (81u16, ws_addr),
+            ]
+            .iter()
+            .copied(),
+        )
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to register onion service: {:?}", e))?;
+    Ok(format!("http://{}", key.public().get_onion_address()))
 }
 
 pub async fn start_rpc_test_network(
@@ -144,36 +399,782 @@ pub async fn start_rpc_test_network_with_fullnode(
     genesis_config: Option<GenesisConfig>,
     fullnode_count: usize,
 ) -> Result<TestNetwork, anyhow::Error> {
-    let network = start_test_network_with_fullnodes(genesis_config, fullnode_count).await?;
-    let working_dir = network.dir();
-    let (server_addr, rpc_server_handle) =
-        start_rpc_gateway(&working_dir.join(SUI_GATEWAY_CONFIG)).await?;
-    let mut wallet_conf: SuiClientConfig =
-        PersistedConfig::read(&working_dir.join(SUI_CLIENT_CONFIG))?;
-    let rpc_url = format!("http://{}", server_addr);
-    let accounts = wallet_conf.accounts.clone();
-    wallet_conf.gateway = GatewayType::RPC(rpc_url.clone());
-    wallet_conf
-        .persisted(&working_dir.join(SUI_CLIENT_CONFIG))
-        .save()?;
-
-    let http_client = HttpClientBuilder::default().build(rpc_url.clone())?;
-    let gateway_client = RpcGatewayClient::new(rpc_url.clone())?;
-    Ok(TestNetwork {
-        network,
-        _rpc_server: rpc_server_handle,
-        accounts,
-        http_client,
-        gateway_client: Arc::new(gateway_client),
-        rpc_url,
-    })
+    TestNetworkBuilder::new()
+        .genesis_config(genesis_config)
+        .fullnode_count(fullnode_count)
+        .build()
+        .await
+}
+
+/// Same as [`start_rpc_test_network_with_fullnode`], but additionally configures the
+/// gateway's Tor/CORS/host behavior via `options`. Passing `RpcGatewayOptions::default()`
+/// reproduces the existing direct-TCP, localhost-only behavior, so callers that don't care
+/// about it are unaffected.
+pub async fn start_rpc_test_network_with_fullnode_and_options(
+    genesis_config: Option<GenesisConfig>,
+    fullnode_count: usize,
+    options: RpcGatewayOptions,
+) -> Result<TestNetwork, anyhow::Error> {
+    TestNetworkBuilder::new()
+        .genesis_config(genesis_config)
+        .fullnode_count(fullnode_count)
+        .rpc_gateway_options(options)
+        .build()
+        .await
+}
+
+/// Where the gateway DB (and, for an in-process backend, the validator/fullnode storage)
+/// lives for a [`TestNetwork`]. Defaults to the original embedded-temp-dir behavior so
+/// existing callers keep working unchanged; a container backend trades that convenience for
+/// a reproducible, externally managed store that CI can run the same way on every machine.
+pub enum TestNetworkBackend {
+    InProcess,
+    /// Launches `image` via testcontainers (the same pairing pattern xmr-btc-swap used for
+    /// bitcoind+electrs on a shared docker network) and points the gateway's
+    /// `GatewayConfig::db_folder_path` at the container's bind-mounted data volume. The
+    /// container is torn down when the returned `TestNetwork` is dropped.
+    Container {
+        image: String,
+        /// Readiness strategy testcontainers waits on before considering `image` up. There's
+        /// no universal signal across images, so this is part of the spec rather than a
+        /// fixed assumption (e.g. a Postgres image never prints "ready").
+        wait_for: testcontainers::images::generic::WaitFor,
+    },
+}
+
+impl Default for TestNetworkBackend {
+    fn default() -> Self {
+        TestNetworkBackend::InProcess
+    }
+}
+
+// One Docker `Cli` for the whole process, not one per container: `Cli` only manages the
+// connection to the local Docker daemon, and every `Container`'s borrow of it just needs a
+// `'static` home to live in `TestNetwork`, so there's no reason to mint a fresh one (and leak
+// it) per `ContainerBackend::start` call.
+static DOCKER_CLI: Lazy<Cli> = Lazy::new(Cli::default);
+
+/// Holds the running container and its host-visible data directory for the lifetime of a
+/// [`TestNetwork`]; dropping it tears the container down via `testcontainers`' own `Drop`
+/// impl, so callers don't need to remember to clean up.
+struct ContainerBackend {
+    _container: Container<'static, GenericImage>,
+    // Kept alongside `_container` (rather than converted to a bare `PathBuf` via
+    // `TempDir::into_path`) so the host-visible data directory is deleted by `TempDir`'s own
+    // `Drop` impl when this backend is torn down, instead of leaking under the OS temp dir
+    // for every container-backed `TestNetwork` ever constructed.
+    _data_dir: tempfile::TempDir,
+    db_folder_path: std::path::PathBuf,
+}
+
+// `image` may be bare ("postgres") or already tagged ("postgres:14"); splitting it here
+// avoids turning a tagged caller-supplied image into a malformed "postgres:14:latest"
+// reference the way a fixed `GenericImage::new(image, "latest")` call would.
+fn split_image_tag(image: &str) -> (&str, &str) {
+    image.split_once(':').unwrap_or((image, "latest"))
+}
+
+impl ContainerBackend {
+    async fn start(
+        image: &str,
+        wait_for: testcontainers::images::generic::WaitFor,
+    ) -> Result<Self, anyhow::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let db_folder_path = data_dir.path().to_path_buf();
+
+        let (name, tag) = split_image_tag(image);
+        let generic_image = GenericImage::new(name, tag)
+            .with_volume(db_folder_path.to_string_lossy(), "/data")
+            .with_wait_for(wait_for);
+        let container = DOCKER_CLI.run(generic_image);
+        Ok(Self {
+            _container: container,
+            _data_dir: data_dir,
+            db_folder_path,
+        })
+    }
+}
+
+/// Builder for [`TestNetwork`], mirroring the `SwarmBuilder` pattern already used for the
+/// underlying validator set. Replaces the growing list of `start_rpc_test_network_with_*`
+/// positional-argument functions (kept above as thin wrappers for existing callers).
+#[derive(Default)]
+pub struct TestNetworkBuilder {
+    genesis_config: Option<GenesisConfig>,
+    fullnode_count: usize,
+    rpc_gateway_options: RpcGatewayOptions,
+    backend: TestNetworkBackend,
+}
+
+impl TestNetworkBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn genesis_config(mut self, genesis_config: Option<GenesisConfig>) -> Self {
+        self.genesis_config = genesis_config;
+        self
+    }
+
+    pub fn fullnode_count(mut self, fullnode_count: usize) -> Self {
+        self.fullnode_count = fullnode_count;
+        self
+    }
+
+    pub fn rpc_gateway_options(mut self, rpc_gateway_options: RpcGatewayOptions) -> Self {
+        self.rpc_gateway_options = rpc_gateway_options;
+        self
+    }
+
+    pub fn backend(mut self, backend: TestNetworkBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub async fn build(self) -> Result<TestNetwork, anyhow::Error> {
+        let network =
+            start_test_network_with_fullnodes(self.genesis_config, self.fullnode_count).await?;
+        let working_dir = network.dir();
+        let gateway_path = working_dir.join(SUI_GATEWAY_CONFIG);
+
+        let container_backend = match self.backend {
+            TestNetworkBackend::InProcess => None,
+            TestNetworkBackend::Container { image, wait_for } => {
+                let backend = ContainerBackend::start(&image, wait_for).await?;
+                let mut gateway_config: GatewayConfig = PersistedConfig::read(&gateway_path)?;
+                gateway_config.db_folder_path = backend.db_folder_path.clone();
+                gateway_config.persisted(&gateway_path).save()?;
+                Some(backend)
+            }
+        };
+
+        self.rpc_gateway_options
+            .persisted(&working_dir.join(RPC_GATEWAY_OPTIONS_FILE))
+            .save()?;
+        let rpc_servers = start_rpc_gateway(&gateway_path).await?;
+        let mut wallet_conf: SuiClientConfig =
+            PersistedConfig::read(&working_dir.join(SUI_CLIENT_CONFIG))?;
+        let rpc_url = format!("http://{}", rpc_servers.http_addr);
+        let ws_url = format!("ws://{}", rpc_servers.ws_addr);
+        let accounts = wallet_conf.accounts.clone();
+        wallet_conf.gateway = GatewayType::RPC(rpc_url.clone());
+        wallet_conf
+            .persisted(&working_dir.join(SUI_CLIENT_CONFIG))
+            .save()?;
+
+        let http_client = HttpClientBuilder::default().build(rpc_url.clone())?;
+        let ws_client = WsClientBuilder::default().build(&ws_url).await?;
+        let gateway_client = RpcGatewayClient::new(rpc_url.clone())?;
+        Ok(TestNetwork {
+            network,
+            _rpc_server: rpc_servers.http_handle,
+            _ws_server: rpc_servers.ws_handle,
+            _container_backend: container_backend,
+            accounts,
+            http_client,
+            ws_client,
+            gateway_client: Arc::new(gateway_client),
+            rpc_url,
+            ws_url,
+            onion_url: rpc_servers.onion_url,
+            notifier: rpc_servers.notifier,
+        })
+    }
 }
 
 pub struct TestNetwork {
     pub network: Swarm,
     _rpc_server: HttpServerHandle,
+    _ws_server: WsServerHandle,
+    // Dropped alongside the rest of `TestNetwork`, which tears down the backing container
+    // (if any) the same way `_rpc_server`/`_ws_server` tear down the listeners.
+    _container_backend: Option<ContainerBackend>,
     pub accounts: Vec<SuiAddress>,
     pub http_client: HttpClient,
+    pub ws_client: WsClient,
     pub gateway_client: GatewayClient,
     pub rpc_url: String,
+    pub ws_url: String,
+    /// `.onion` address the gateway is reachable at, if it was started with a Tor control
+    /// port; `None` for networks started without Tor.
+    pub onion_url: Option<String>,
+    /// Publishes to `subscribeTransaction`/`subscribeEvent` subscribers on the WS listener.
+    /// Tests use this to exercise the subscription path directly, since the real
+    /// commit/event pipeline that would drive it in production lives outside this crate.
+    pub notifier: Arc<GatewayNotifier>,
+}
+
+impl TestNetwork {
+    /// Performs the `init_secure_api` ECDH handshake against this network's gateway and
+    /// returns a client wrapper that transparently encrypts/decrypts every call through the
+    /// `encrypted_request` envelope, so tests can exercise the secure channel the same way
+    /// they exercise the plaintext one via `http_client`.
+    pub async fn secure_client(&self) -> Result<secure::SecureRpcClient, anyhow::Error> {
+        secure::SecureRpcClient::handshake(self.http_client.clone()).await
+    }
+}
+
+/// Encrypted JSON-RPC channel: an opt-in transport negotiated via `init_secure_api` (an
+/// ECDH key exchange) and carried afterwards as `encrypted_request` envelopes, modeled on
+/// grin-wallet's `init_api_secure`. Both halves live here: [`register_secure_channel`] is
+/// called from `start_rpc_gateway` to register the server side on the gateway's
+/// `RpcModule`s alongside the other API implementations, and [`SecureRpcClient`] is the
+/// client half `TestNetwork::secure_client` hands back to callers.
+pub mod secure {
+    use super::{HttpClient, RpcModule};
+    use aes_gcm::aead::{Aead, NewAead};
+    use aes_gcm::{Aes256Gcm, Key, Nonce};
+    use hkdf::Hkdf;
+    use jsonrpsee_core::client::ClientT;
+    use jsonrpsee_core::params::ToRpcParams;
+    use jsonrpsee_core::rpc_params;
+    use rand::rngs::OsRng;
+    use secp256k1::ecdh::SharedSecret;
+    use secp256k1::{PublicKey, Secp256k1, SecretKey};
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+    use sha2::Sha256;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use tokio::sync::Mutex as AsyncMutex;
+
+    const AES_KEY_LEN: usize = 32;
+    const NONCE_LEN: usize = 12;
+    const HKDF_INFO: &[u8] = b"sui-rpc-gateway-secure-channel";
+
+    #[derive(Serialize, Deserialize)]
+    struct InitSecureApiParams {
+        public_key: String,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct InitSecureApiResult {
+        public_key: String,
+    }
+
+    #[derive(Serialize, Deserialize, Clone)]
+    struct EncryptedEnvelope {
+        // Identifies which handshake's derived key this envelope was encrypted/should be
+        // decrypted with; set to the server's ephemeral public key from `init_secure_api`.
+        session_id: String,
+        // base64(nonce || ciphertext)
+        payload: String,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct InnerRequest {
+        method: String,
+        params: Value,
+    }
+
+    fn to_rpc_err(err: impl std::fmt::Display) -> jsonrpsee_core::Error {
+        jsonrpsee_core::Error::Custom(err.to_string())
+    }
+
+    // Wraps an already-decoded `Value` so it can be passed as params to `RpcModule::call`,
+    // which otherwise expects a `ToRpcParams` built via `rpc_params!`.
+    struct RawParams(Value);
+
+    impl ToRpcParams for RawParams {
+        fn to_rpc_params(
+            self,
+        ) -> Result<Option<Box<serde_json::value::RawValue>>, serde_json::Error> {
+            if self.0.is_null() {
+                Ok(None)
+            } else {
+                Ok(Some(serde_json::value::to_raw_value(&self.0)?))
+            }
+        }
+    }
+
+    struct SecureSession {
+        key: Key<Aes256Gcm>,
+        reply_nonce_counter: AtomicU64,
+        // Edge case: nonce reuse detection. `None` until the first request is accepted;
+        // after that, the highest nonce seen so far. A legitimate client's request nonce
+        // only increases, so any envelope whose nonce is at or below the high-water mark
+        // (including a replay of that exact nonce) is rejected rather than decrypted.
+        last_seen_request_nonce: std::sync::Mutex<Option<u64>>,
+    }
+
+    struct SecureChannelState {
+        // The already-built module (the same `RpcGatewayImpl`/etc. methods as the plaintext
+        // transport) that decrypted requests are dispatched into.
+        dispatch: Arc<RpcModule<()>>,
+        // Handshake state keyed per-connection: each `init_secure_api` call mints a fresh
+        // session keyed by the server's ephemeral public key for that handshake.
+        sessions: AsyncMutex<HashMap<String, Arc<SecureSession>>>,
+    }
+
+    /// Registers `init_secure_api` and `encrypted_request` on `module`, dispatching
+    /// decrypted requests into `dispatch` and re-encrypting the result. A decryption
+    /// failure (bad key, truncated payload, replayed nonce) surfaces as a regular JSON-RPC
+    /// error, never a panic.
+    pub(crate) fn register_secure_channel(
+        module: &mut RpcModule<()>,
+        dispatch: Arc<RpcModule<()>>,
+    ) -> Result<(), anyhow::Error> {
+        let state = Arc::new(SecureChannelState {
+            dispatch,
+            sessions: AsyncMutex::new(HashMap::new()),
+        });
+
+        let handshake_state = state.clone();
+        module.register_async_method("init_secure_api", move |params, _ctx| {
+            let state = handshake_state.clone();
+            async move {
+                let params: InitSecureApiParams = params.parse().map_err(to_rpc_err)?;
+                let client_public_bytes = hex::decode(&params.public_key).map_err(to_rpc_err)?;
+                let client_public =
+                    PublicKey::from_slice(&client_public_bytes).map_err(to_rpc_err)?;
+
+                let secp = Secp256k1::new();
+                let server_secret = SecretKey::new(&mut OsRng);
+                let server_public = PublicKey::from_secret_key(&secp, &server_secret);
+
+                let shared_secret = SharedSecret::new(&client_public, &server_secret);
+                let hk = Hkdf::<Sha256>::new(None, shared_secret.as_ref());
+                let mut key_bytes = [0u8; AES_KEY_LEN];
+                hk.expand(HKDF_INFO, &mut key_bytes)
+                    .map_err(|_| to_rpc_err("HKDF expand failed"))?;
+
+                let session_id = hex::encode(server_public.serialize());
+                state.sessions.lock().await.insert(
+                    session_id.clone(),
+                    Arc::new(SecureSession {
+                        key: *Key::<Aes256Gcm>::from_slice(&key_bytes),
+                        reply_nonce_counter: AtomicU64::new(0),
+                        last_seen_request_nonce: std::sync::Mutex::new(None),
+                    }),
+                );
+
+                Ok(InitSecureApiResult {
+                    public_key: session_id,
+                })
+            }
+        })?;
+
+        let request_state = state;
+        module.register_async_method("encrypted_request", move |params, _ctx| {
+            let state = request_state.clone();
+            async move {
+                let envelope: EncryptedEnvelope = params.parse().map_err(to_rpc_err)?;
+                let session = state
+                    .sessions
+                    .lock()
+                    .await
+                    .get(&envelope.session_id)
+                    .cloned()
+                    .ok_or_else(|| to_rpc_err("unknown secure session"))?;
+
+                let raw = base64::decode(&envelope.payload).map_err(to_rpc_err)?;
+                if raw.len() < NONCE_LEN {
+                    return Err(to_rpc_err("encrypted request shorter than the nonce prefix"));
+                }
+                let (nonce_bytes, ciphertext) = raw.split_at(NONCE_LEN);
+                let mut counter_bytes = [0u8; 8];
+                counter_bytes.copy_from_slice(&nonce_bytes[..8]);
+                let counter = u64::from_be_bytes(counter_bytes);
+                if matches!(*session.last_seen_request_nonce.lock().unwrap(), Some(seen) if counter <= seen)
+                {
+                    return Err(to_rpc_err("nonce reuse detected"));
+                }
+
+                let cipher = Aes256Gcm::new(&session.key);
+                let plaintext = cipher
+                    .decrypt(Nonce::from_slice(nonce_bytes), ciphertext)
+                    .map_err(|_| to_rpc_err("failed to decrypt request"))?;
+                {
+                    // Only an envelope that decrypted is accepted and advances the mark.
+                    let mut last_seen = session.last_seen_request_nonce.lock().unwrap();
+                    if matches!(*last_seen, Some(seen) if counter <= seen) {
+                        return Err(to_rpc_err("nonce reuse detected"));
+                    }
+                    *last_seen = Some(counter);
+                }
+                let inner: InnerRequest = serde_json::from_slice(&plaintext).map_err(to_rpc_err)?;
+
+                let result: Value = state
+                    .dispatch
+                    .call(&inner.method, RawParams(inner.params))
+                    .await
+                    .map_err(to_rpc_err)?;
+
+                let reply_plaintext = serde_json::to_vec(&result).map_err(to_rpc_err)?;
+                let reply_counter = session.reply_nonce_counter.fetch_add(1, Ordering::SeqCst);
+                let mut reply_nonce_bytes = [0u8; NONCE_LEN];
+                // Replies count down from `u64::MAX` so the request and reply nonce spaces
+                // can never collide even though a session reuses one AES-GCM key for both.
+                reply_nonce_bytes[..8]
+                    .copy_from_slice(&(u64::MAX - reply_counter).to_be_bytes());
+                let reply_ciphertext = cipher
+                    .encrypt(
+                        Nonce::from_slice(&reply_nonce_bytes),
+                        reply_plaintext.as_ref(),
+                    )
+                    .map_err(|_| to_rpc_err("failed to encrypt reply"))?;
+
+                let mut reply_payload = Vec::with_capacity(NONCE_LEN + reply_ciphertext.len());
+                reply_payload.extend_from_slice(&reply_nonce_bytes);
+                reply_payload.extend_from_slice(&reply_ciphertext);
+
+                Ok(EncryptedEnvelope {
+                    session_id: envelope.session_id,
+                    payload: base64::encode(reply_payload),
+                })
+            }
+        })?;
+
+        Ok(())
+    }
+
+    /// Client-side half of the ECDH handshake: generates an ephemeral secp256k1 keypair,
+    /// exchanges public keys with the gateway via `init_secure_api`, then derives the same
+    /// AES-256-GCM key the server derives (ECDH shared point run through HKDF/SHA-256).
+    pub struct SecureRpcClient {
+        inner: HttpClient,
+        session_id: String,
+        key: Key<Aes256Gcm>,
+        // Monotonic per-connection nonce counter. Using a counter instead of random bytes
+        // means nonce reuse can only happen if the connection outlives 2^64 messages.
+        nonce_counter: AtomicU64,
+    }
+
+    impl SecureRpcClient {
+        pub async fn handshake(inner: HttpClient) -> Result<Self, anyhow::Error> {
+            let secp = Secp256k1::new();
+            let client_secret = SecretKey::new(&mut OsRng);
+            let client_public = PublicKey::from_secret_key(&secp, &client_secret);
+
+            let params = InitSecureApiParams {
+                public_key: hex::encode(client_public.serialize()),
+            };
+            let result: InitSecureApiResult = inner
+                .request("init_secure_api", rpc_params![params])
+                .await?;
+            let server_public = PublicKey::from_slice(&hex::decode(&result.public_key)?)?;
+
+            let shared_secret = SharedSecret::new(&server_public, &client_secret);
+            let hk = Hkdf::<Sha256>::new(None, shared_secret.as_ref());
+            let mut key_bytes = [0u8; AES_KEY_LEN];
+            hk.expand(HKDF_INFO, &mut key_bytes)
+                .map_err(|_| anyhow::anyhow!("HKDF expand failed"))?;
+
+            Ok(Self {
+                inner,
+                session_id: result.public_key,
+                key: *Key::<Aes256Gcm>::from_slice(&key_bytes),
+                nonce_counter: AtomicU64::new(0),
+            })
+        }
+
+        /// Encrypts `(method, params)` as an `encrypted_request` envelope, sends it, and
+        /// decrypts the reply. A decryption failure on the reply surfaces as an
+        /// `anyhow::Error` like any other RPC failure, never a panic.
+        pub async fn request(&self, method: &str, params: Value) -> Result<Value, anyhow::Error> {
+            let cipher = Aes256Gcm::new(&self.key);
+
+            let counter = self.nonce_counter.fetch_add(1, Ordering::SeqCst);
+            let mut nonce_bytes = [0u8; NONCE_LEN];
+            nonce_bytes[..8].copy_from_slice(&counter.to_be_bytes());
+            let nonce = Nonce::from_slice(&nonce_bytes);
+
+            let body = InnerRequest {
+                method: method.to_string(),
+                params,
+            };
+            let plaintext = serde_json::to_vec(&body)?;
+            let ciphertext = cipher
+                .encrypt(nonce, plaintext.as_ref())
+                .map_err(|_| anyhow::anyhow!("failed to encrypt request"))?;
+
+            let mut payload = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+            payload.extend_from_slice(&nonce_bytes);
+            payload.extend_from_slice(&ciphertext);
+            let envelope = EncryptedEnvelope {
+                session_id: self.session_id.clone(),
+                payload: base64::encode(payload),
+            };
+
+            let reply: EncryptedEnvelope = self
+                .inner
+                .request("encrypted_request", rpc_params![envelope])
+                .await?;
+            let raw = base64::decode(reply.payload)?;
+            if raw.len() < NONCE_LEN {
+                anyhow::bail!("encrypted reply shorter than the nonce prefix");
+            }
+            let (reply_nonce, reply_ciphertext) = raw.split_at(NONCE_LEN);
+            let plaintext = cipher
+                .decrypt(Nonce::from_slice(reply_nonce), reply_ciphertext)
+                .map_err(|_| anyhow::anyhow!("failed to decrypt gateway response"))?;
+            Ok(serde_json::from_slice(&plaintext)?)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use jsonrpsee_http_client::HttpClientBuilder;
+        use jsonrpsee_http_server::HttpServerBuilder;
+
+        #[tokio::test]
+        async fn replayed_nonce_is_rejected() {
+            let dispatch = Arc::new({
+                let mut m = RpcModule::new(());
+                m.register_method("echo", |params, _| {
+                    let value: serde_json::Value = params.one()?;
+                    Ok(value)
+                })
+                .unwrap();
+                m
+            });
+
+            let mut module = RpcModule::new(());
+            register_secure_channel(&mut module, dispatch).unwrap();
+
+            let server = HttpServerBuilder::default()
+                .build("127.0.0.1:0")
+                .await
+                .unwrap();
+            let addr = server.local_addr().unwrap();
+            let _handle = server.start(module).unwrap();
+
+            let http_client = HttpClientBuilder::default()
+                .build(format!("http://{}", addr))
+                .unwrap();
+            let client = SecureRpcClient::handshake(http_client).await.unwrap();
+
+            // The first request, under nonce 0, succeeds.
+            let first = client.request("echo", serde_json::json!("hello")).await;
+            assert!(first.is_ok());
+
+            // Re-encrypt a second request under that same nonce 0 and replay it directly
+            // against the server, bypassing `SecureRpcClient`'s own incrementing counter.
+            // The server must reject it even though the plaintext differs from the first.
+            let cipher = Aes256Gcm::new(&client.key);
+            let nonce_bytes = [0u8; NONCE_LEN];
+            let body = InnerRequest {
+                method: "echo".to_string(),
+                params: serde_json::json!("replayed"),
+            };
+            let plaintext = serde_json::to_vec(&body).unwrap();
+            let ciphertext = cipher
+                .encrypt(Nonce::from_slice(&nonce_bytes), plaintext.as_ref())
+                .unwrap();
+            let mut payload = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+            payload.extend_from_slice(&nonce_bytes);
+            payload.extend_from_slice(&ciphertext);
+            let envelope = EncryptedEnvelope {
+                session_id: client.session_id.clone(),
+                payload: base64::encode(payload),
+            };
+
+            let replayed: Result<EncryptedEnvelope, _> = client
+                .inner
+                .request("encrypted_request", rpc_params![envelope])
+                .await;
+            assert!(replayed.is_err());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use jsonrpsee_core::client::{Subscription, SubscriptionClientT};
+    use jsonrpsee_core::rpc_params;
+
+    #[tokio::test]
+    async fn subscribe_transaction_receives_published_notification() {
+        let notifier = Arc::new(GatewayNotifier::new());
+        let mut module = RpcModule::new(());
+        register_subscriptions(&mut module, notifier.clone()).unwrap();
+
+        let server = WsServerBuilder::default().build("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let _handle = server.start(module).unwrap();
+
+        let client = WsClientBuilder::default()
+            .build(format!("ws://{}", addr))
+            .await
+            .unwrap();
+        let mut subscription: Subscription<serde_json::Value> = client
+            .subscribe(
+                "subscribeTransaction",
+                rpc_params![],
+                "unsubscribeTransaction",
+            )
+            .await
+            .unwrap();
+
+        let published = serde_json::json!({"digest": "abc"});
+        notifier.publish_transaction(published.clone());
+
+        let received = subscription.next().await.unwrap().unwrap();
+        assert_eq!(received, published);
+    }
+
+    #[tokio::test]
+    async fn secure_channel_round_trips_through_dispatch() {
+        let dispatch = Arc::new({
+            let mut dispatch_module = RpcModule::new(());
+            dispatch_module
+                .register_method("echo", |params, _| {
+                    let value: serde_json::Value = params.one()?;
+                    Ok(value)
+                })
+                .unwrap();
+            dispatch_module
+        });
+
+        let mut module = RpcModule::new(());
+        secure::register_secure_channel(&mut module, dispatch).unwrap();
+
+        let server = HttpServerBuilder::default()
+            .build("127.0.0.1:0")
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        let _handle = server.start(module).unwrap();
+
+        let http_client = HttpClientBuilder::default()
+            .build(format!("http://{}", addr))
+            .unwrap();
+        let secure_client = secure::SecureRpcClient::handshake(http_client).await.unwrap();
+
+        let result = secure_client
+            .request("echo", serde_json::json!("hello"))
+            .await
+            .unwrap();
+        assert_eq!(result, serde_json::json!("hello"));
+    }
+
+    #[tokio::test]
+    async fn cors_preflight_allows_permitted_origin_and_rejects_others() {
+        let options = RpcGatewayOptions {
+            cors_allowed_origins: vec!["http://allowed.example".to_string()],
+            ..Default::default()
+        };
+        let access_control = build_access_control(&options).unwrap();
+        let server = HttpServerBuilder::default()
+            .set_access_control(access_control)
+            .build("127.0.0.1:0")
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        let _handle = server.start(RpcModule::new(())).unwrap();
+
+        let preflight = |origin: &str| {
+            hyper::Request::builder()
+                .method(hyper::Method::OPTIONS)
+                .uri(format!("http://{}", addr))
+                .header("origin", origin)
+                .header("access-control-request-method", "POST")
+                .body(hyper::Body::empty())
+                .unwrap()
+        };
+
+        let client = hyper::Client::new();
+        let allowed = client.request(preflight("http://allowed.example")).await.unwrap();
+        assert!(allowed.headers().contains_key("access-control-allow-origin"));
+
+        let rejected = client
+            .request(preflight("http://not-allowed.example"))
+            .await
+            .unwrap();
+        assert!(!rejected.headers().contains_key("access-control-allow-origin"));
+    }
+
+    #[tokio::test]
+    async fn rpc_gateway_options_round_trip_through_sidecar_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let options_path = dir.path().join(RPC_GATEWAY_OPTIONS_FILE);
+
+        let options = RpcGatewayOptions {
+            cors_allowed_origins: vec!["http://allowed.example".to_string()],
+            allowed_hosts: vec!["example.com".to_string()],
+            cors_allowed_headers: vec!["content-type".to_string()],
+            tor_control_port: None,
+        };
+        options.persisted(&options_path).save().unwrap();
+
+        let loaded: RpcGatewayOptions = PersistedConfig::read(&options_path).unwrap();
+        assert_eq!(loaded.cors_allowed_origins, options.cors_allowed_origins);
+        assert_eq!(loaded.allowed_hosts, options.allowed_hosts);
+        assert_eq!(loaded.cors_allowed_headers, options.cors_allowed_headers);
+    }
+
+    #[tokio::test]
+    async fn cors_preflight_allows_permitted_header_and_rejects_others() {
+        let options = RpcGatewayOptions {
+            cors_allowed_origins: vec!["http://allowed.example".to_string()],
+            cors_allowed_headers: vec!["content-type".to_string()],
+            ..Default::default()
+        };
+        let access_control = build_access_control(&options).unwrap();
+        let server = HttpServerBuilder::default()
+            .set_access_control(access_control)
+            .build("127.0.0.1:0")
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        let _handle = server.start(RpcModule::new(())).unwrap();
+
+        let preflight = |header: &str| {
+            hyper::Request::builder()
+                .method(hyper::Method::OPTIONS)
+                .uri(format!("http://{}", addr))
+                .header("origin", "http://allowed.example")
+                .header("access-control-request-method", "POST")
+                .header("access-control-request-headers", header)
+                .body(hyper::Body::empty())
+                .unwrap()
+        };
+
+        let client = hyper::Client::new();
+        let allowed = client.request(preflight("content-type")).await.unwrap();
+        let allowed_headers = match allowed.headers().get("access-control-allow-headers") {
+            Some(value) => value.to_str().unwrap().to_lowercase(),
+            None => String::new(),
+        };
+        assert!(allowed_headers.contains("content-type"));
+
+        let rejected = client.request(preflight("x-not-allowed")).await.unwrap();
+        let rejected_headers = match rejected.headers().get("access-control-allow-headers") {
+            Some(value) => value.to_str().unwrap().to_lowercase(),
+            None => String::new(),
+        };
+        assert!(!rejected_headers.contains("x-not-allowed"));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires docker"]
+    async fn container_backend_starts_and_shares_one_process_wide_cli() {
+        let wait_for = testcontainers::images::generic::WaitFor::message_on_stdout("ready");
+        let first = ContainerBackend::start("busybox", wait_for.clone()).await.unwrap();
+        let second = ContainerBackend::start("busybox", wait_for).await.unwrap();
+        assert!(first.db_folder_path.exists());
+        assert!(second.db_folder_path.exists());
+        assert_ne!(first.db_folder_path, second.db_folder_path);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires docker"]
+    async fn container_backend_deletes_its_data_dir_on_drop() {
+        let wait_for = testcontainers::images::generic::WaitFor::message_on_stdout("ready");
+        let backend = ContainerBackend::start("busybox", wait_for).await.unwrap();
+        let db_folder_path = backend.db_folder_path.clone();
+        assert!(db_folder_path.exists());
+        drop(backend);
+        assert!(!db_folder_path.exists());
+    }
+
+    #[test]
+    fn split_image_tag_defaults_to_latest_for_bare_images() {
+        assert_eq!(split_image_tag("busybox"), ("busybox", "latest"));
+        assert_eq!(split_image_tag("postgres:14"), ("postgres", "14"));
+    }
 }